@@ -12,20 +12,25 @@ use embassy_time::{Duration, Timer};
 use esp_radio::ble::controller::BleConnector;
 use trouble_host::prelude::*;
 
-use esp_hal::analog::adc::{AdcConfig, Attenuation};
+use esp_hal::analog::adc::AdcConfig;
 use esp_hal::clock::CpuClock;
-use esp_hal::gpio::{DriveMode, Input, InputConfig, Output, OutputConfig};
+use esp_hal::gpio::{DriveMode, Input, InputConfig, Level, Output, OutputConfig};
 use esp_hal::ledc::channel::ChannelIFace;
 use esp_hal::ledc::timer::TimerIFace;
 use esp_hal::ledc::{HighSpeed, LSGlobalClkSource, Ledc, LowSpeed, channel, timer};
+use esp_hal::rng::Rng;
+use esp_hal::spi::master::{Config as SpiConfig, Spi};
 use esp_hal::time::Rate;
 use esp_hal::timer::timg::TimerGroup;
 use esp_println as _;
 
 use lib::ble;
+use lib::ethernet;
 use lib::pin::{
     AdcReadPinTaskItem, BasicReadPinTaskItem, BasicWritePinTaskItem, PWMWritePinTaskItem,
 };
+use lib::web::{self, WebApp};
+use lib::wifi::{self, NetMode};
 
 use webserver_html as lib;
 
@@ -35,22 +40,12 @@ fn panic(_: &core::panic::PanicInfo) -> ! {
 }
 
 extern crate alloc;
-use alloc::string::String;
 use alloc::vec::Vec;
 
 // This creates a default app-descriptor required by the esp-idf bootloader.
 // For more information see: <https://docs.espressif.com/projects/esp-idf/en/stable/esp32/api-reference/system/app_image_format.html#application-description>
 esp_bootloader_esp_idf::esp_app_desc!();
 
-#[derive(serde::Deserialize)]
-struct Config {
-    bluetooth_name: String,
-    basic_write_pin_nums: Vec<u8>,
-    pwm_write_pin_nums: Vec<u8>,
-    basic_read_pin_nums: Vec<u8>,
-    adc_read_pin_nums: Vec<u8>,
-}
-
 #[esp_rtos::main]
 async fn main(spawner: Spawner) -> ! {
     // generator version: 1.0.0
@@ -58,13 +53,19 @@ async fn main(spawner: Spawner) -> ! {
     let config = esp_hal::Config::default().with_cpu_clock(CpuClock::max());
     let peripherals = esp_hal::init(config);
 
-    esp_alloc::heap_allocator!(#[unsafe(link_section = ".dram2_uninit")] size: 98767);
+    // Wi-Fi and BLE run concurrently off the same radio below (coexistence), which needs more
+    // heap than either stack alone: embassy-net's StackResources<3>, picoserve's per-connection
+    // buffers and trouble-host's HostResources are all live at once, on top of the BLE/Wi-Fi
+    // driver's own allocations.
+    esp_alloc::heap_allocator!(#[unsafe(link_section = ".dram2_uninit")] size: 131072);
 
     let timg0 = TimerGroup::new(peripherals.TIMG0);
     esp_rtos::start(timg0.timer0);
 
     info!("Embassy initialized!");
 
+    // A single `esp_radio::Controller` backs both the BLE transport and the Wi-Fi stack below -
+    // this is the esp-wifi/BLE coexistence mode, not two independent radios.
     let radio_init = &*lib::mk_static!(
         esp_radio::Controller<'static>,
         esp_radio::init().expect("Failed to initialize Wi-Fi/BLE controller")
@@ -72,45 +73,118 @@ async fn main(spawner: Spawner) -> ! {
     let transport = BleConnector::new(&radio_init, peripherals.BT, Default::default()).unwrap();
     let ble_controller = ExternalController::<_, 64>::new(transport);
 
-    let config_data = include_bytes!("../config.json");
-    let config_string = String::from_utf8(config_data.to_vec()).unwrap();
-    let (config, _len) = serde_json_core::from_str::<Config>(&config_string).unwrap();
+    let rng = Rng::new(peripherals.RNG);
 
-    let basic_write_pin_nums = config.basic_write_pin_nums;
-    let pwm_write_pin_nums = config.pwm_write_pin_nums;
-    let basic_read_pin_nums = config.basic_read_pin_nums;
-    let adc_read_pin_nums = config.adc_read_pin_nums;
-    let bluetooth_name = config.bluetooth_name;
+    // Pin roles and the advertised name come from the flash-persisted board config (see
+    // `lib::config`), not a compile-time `config.json` - `POST /config` and the BLE config
+    // characteristic update it in place, taking effect on the next boot.
+    let board_config = lib::config::load();
+    let bluetooth_name = board_config.bluetooth_name;
 
-    // Wrap peripherals in Option so we can take them once in the loop
-    let mut gpio14 = Some(peripherals.GPIO14);
-    let mut gpio26 = Some(peripherals.GPIO26);
-    let mut gpio25 = Some(peripherals.GPIO25);
-    let mut gpio32 = Some(peripherals.GPIO32);
-    let mut gpio35 = Some(peripherals.GPIO35);
-    let mut gpio33 = Some(peripherals.GPIO33);
+    let mut basic_write_pin_nums: Vec<u8> = Vec::new();
+    let mut pwm_write_pin_nums: Vec<u8> = Vec::new();
+    let mut basic_read_pin_nums: Vec<u8> = Vec::new();
+    let mut adc_read_pin_nums: Vec<u8> = Vec::new();
+    for (pin_num, role) in board_config.pins {
+        match role {
+            lib::config::PinRole::Output => basic_write_pin_nums.push(pin_num),
+            lib::config::PinRole::Pwm => pwm_write_pin_nums.push(pin_num),
+            lib::config::PinRole::Input => basic_read_pin_nums.push(pin_num),
+            lib::config::PinRole::Adc => adc_read_pin_nums.push(pin_num),
+        }
+    }
+
+    // A board is either a Wi-Fi STA (serving HTTP over Wi-Fi below) or an ESP-NOW peer - both
+    // modes need exclusive use of the `WIFI` peripheral, so `ESP_NOW_PEER` (a peer MAC address,
+    // "AA:BB:CC:DD:EE:FF") switches this board into ESP-NOW instead of bringing up Wi-Fi.
+    let web_app = WebApp::default();
+    if let Some(peer_mac) = option_env!("ESP_NOW_PEER") {
+        let peer = lib::esp_now::parse_mac(peer_mac)
+            .expect("ESP_NOW_PEER must be a MAC address, e.g. AA:BB:CC:DD:EE:FF");
+        let esp_now = lib::esp_now::init(radio_init, peripherals.WIFI);
+        spawner.must_spawn(lib::esp_now::esp_now_task(
+            esp_now,
+            peer,
+            adc_read_pin_nums.clone(),
+        ));
+    } else {
+        let stack = wifi::start_wifi(
+            radio_init,
+            peripherals.WIFI,
+            rng,
+            &spawner,
+            NetMode::from_config(
+                board_config.static_ip.as_deref(),
+                board_config.gateway_ip.as_deref(),
+            ),
+        )
+        .await;
+        for task_id in web::WIFI_WEB_TASK_IDS {
+            spawner.must_spawn(web::web_task(task_id, stack, web_app.router, web_app.config));
+        }
+    }
+
+    // Wired Ethernet link over a WIZnet W5500 on SPI2. Runs the same `Application` router as the
+    // Wi-Fi stack above, just on its own slice of the `web_task` pool. It is not a fallback for
+    // Wi-Fi: it starts only after `start_wifi` above has returned (which waits for a Wi-Fi
+    // address), and `start_ethernet` in turn waits for the W5500, a link and an address before
+    // boot continues.
+    let spi = Spi::new(peripherals.SPI2, SpiConfig::default().with_frequency(Rate::from_mhz(20)))
+        .unwrap()
+        .with_sck(peripherals.GPIO18)
+        .with_mosi(peripherals.GPIO23)
+        .with_miso(peripherals.GPIO19);
+    let cs = Output::new(peripherals.GPIO5, Level::High, OutputConfig::default());
+    let spi_device = embedded_hal_bus::spi::ExclusiveDevice::new_no_delay(spi, cs).unwrap();
+    let eth_int = Input::new(peripherals.GPIO4, InputConfig::default());
+    let eth_reset = Output::new(peripherals.GPIO16, Level::High, OutputConfig::default());
+    let eth_mac_addr = {
+        let mut mac = esp_hal::efuse::Efuse::read_base_mac_address();
+        mac[5] |= 0xc1;
+        mac
+    };
+    let eth_seed = rng.random() as u64 | ((rng.random() as u64) << 32);
+    let eth_stack = ethernet::start_ethernet(
+        eth_mac_addr,
+        spi_device,
+        eth_int,
+        eth_reset,
+        eth_seed,
+        &spawner,
+        NetMode::from_config(
+            board_config.static_ip.as_deref(),
+            board_config.gateway_ip.as_deref(),
+        ),
+    )
+    .await;
+    for task_id in web::ETH_WEB_TASK_IDS {
+        spawner.must_spawn(web::web_task(task_id, eth_stack, web_app.router, web_app.config));
+    }
+
+    // A single registry owns every usable pin, so each category below just asks for a pin by
+    // number instead of repeating its own `match pin_num { 14 => ..., 26 => ... }` block.
+    let mut pin_registry = lib::pin::PinRegistry::new(
+        peripherals.GPIO14,
+        peripherals.GPIO26,
+        peripherals.GPIO25,
+        peripherals.GPIO33,
+        peripherals.GPIO32,
+        peripherals.GPIO35,
+    );
+
+    // Pin numbers the BLE `pin_data_output` characteristic streams to subscribed centrals -
+    // every basic digital pin, whichever direction it's configured for.
+    let basic_pin_nums: Vec<u8> = basic_write_pin_nums
+        .iter()
+        .chain(basic_read_pin_nums.iter())
+        .copied()
+        .collect();
 
     // Basic write pins
     let mut basic_write_pins: Vec<BasicWritePinTaskItem> =
         Vec::with_capacity(basic_write_pin_nums.len());
     for pin_num in basic_write_pin_nums {
-        // Map pin number to actual peripheral - expand this for more pins
-        let pin = match pin_num {
-            14 => gpio14
-                .take()
-                .map(|p| Output::new(p, esp_hal::gpio::Level::Low, OutputConfig::default())),
-            26 => gpio26
-                .take()
-                .map(|p| Output::new(p, esp_hal::gpio::Level::Low, OutputConfig::default())),
-            25 => gpio25
-                .take()
-                .map(|p| Output::new(p, esp_hal::gpio::Level::Low, OutputConfig::default())),
-            33 => gpio33
-                .take()
-                .map(|p| Output::new(p, esp_hal::gpio::Level::Low, OutputConfig::default())),
-            _ => None,
-        };
-        if let Some(pin) = pin {
+        if let Some(pin) = pin_registry.take_output(pin_num) {
             basic_write_pins.push(BasicWritePinTaskItem { pin_num, pin });
         }
     }
@@ -119,14 +193,7 @@ async fn main(spawner: Spawner) -> ! {
     let mut basic_read_pins: Vec<BasicReadPinTaskItem> =
         Vec::with_capacity(basic_read_pin_nums.len());
     for pin_num in basic_read_pin_nums {
-        let pin = match pin_num {
-            14 => gpio14.take().map(|p| Input::new(p, InputConfig::default())),
-            26 => gpio26.take().map(|p| Input::new(p, InputConfig::default())),
-            25 => gpio25.take().map(|p| Input::new(p, InputConfig::default())),
-            33 => gpio33.take().map(|p| Input::new(p, InputConfig::default())),
-            _ => None,
-        };
-        if let Some(pin) = pin {
+        if let Some(pin) = pin_registry.take_input(pin_num) {
             basic_read_pins.push(BasicReadPinTaskItem { pin_num, pin });
         }
     }
@@ -157,23 +224,7 @@ async fn main(spawner: Spawner) -> ! {
 
     let mut pwm_write_pins: Vec<PWMWritePinTaskItem> = Vec::with_capacity(pwm_write_pin_nums.len());
     for pin_num in pwm_write_pin_nums {
-        // Map pin number to actual peripheral - expand this for more pins
-        let pin = match pin_num {
-            14 => gpio14
-                .take()
-                .map(|p| Output::new(p, esp_hal::gpio::Level::Low, OutputConfig::default())),
-            26 => gpio26
-                .take()
-                .map(|p| Output::new(p, esp_hal::gpio::Level::Low, OutputConfig::default())),
-            25 => gpio25
-                .take()
-                .map(|p| Output::new(p, esp_hal::gpio::Level::Low, OutputConfig::default())),
-            33 => gpio33
-                .take()
-                .map(|p| Output::new(p, esp_hal::gpio::Level::Low, OutputConfig::default())),
-            _ => None,
-        };
-        if let Some(pin) = pin {
+        if let Some(pin) = pin_registry.take_output(pin_num) {
             let mut ch = ledc.channel(CHANNELS[channel_idx], pin);
             ch.configure(channel::config::Config {
                 timer: hstimer0,
@@ -192,28 +243,18 @@ async fn main(spawner: Spawner) -> ! {
     let mut adc_config = AdcConfig::new();
     let mut adc_read_pins: Vec<AdcReadPinTaskItem> = Vec::with_capacity(adc_read_pin_nums.len());
     for pin_num in adc_read_pin_nums.clone() {
-        let pin = match pin_num {
-            32 => gpio32.take().map(|p| {
-                let adc_pin = adc_config.enable_pin(p, Attenuation::_11dB);
-                let adc_read_pin = AdcReadPinTaskItem {
-                    pin_num,
-                    gpio32: Some(adc_pin),
-                    gpio35: None,
-                };
-                adc_read_pin
-            }),
-            35 => gpio35.take().map(|p| {
-                let adc_pin = adc_config.enable_pin(p, Attenuation::_11dB);
-                let adc_read_pin = AdcReadPinTaskItem {
-                    pin_num,
-                    gpio32: None,
-                    gpio35: Some(adc_pin),
-                };
-                adc_read_pin
-            }),
-            _ => None,
-        };
-        if let Some(pin) = pin {
+        let filter_coeffs = board_config
+            .adc_filters
+            .iter()
+            .find(|(num, _)| *num == pin_num)
+            .map(|(_, cutoff_hz)| {
+                lib::filter::BiquadCoeffs::single_pole_low_pass(
+                    *cutoff_hz,
+                    lib::pin::ADC_SAMPLE_RATE_HZ,
+                )
+            })
+            .unwrap_or(lib::filter::BiquadCoeffs::PASS_THROUGH);
+        if let Some(pin) = pin_registry.take_adc(pin_num, &mut adc_config, filter_coeffs) {
             adc_read_pins.push(pin);
         }
     }
@@ -232,7 +273,14 @@ async fn main(spawner: Spawner) -> ! {
         adc_config,
     ));
 
-    ble::run(ble_controller, &bluetooth_name, adc_read_pin_nums).await;
+    ble::run(
+        ble_controller,
+        &bluetooth_name,
+        basic_pin_nums,
+        adc_read_pin_nums,
+        board_config.battery_pin,
+    )
+    .await;
 
     let mut loop_count = 0;
     loop {
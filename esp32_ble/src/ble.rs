@@ -1,4 +1,4 @@
-use core::sync::atomic::Ordering;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 use defmt::{info, warn};
 
@@ -34,30 +34,102 @@ struct PinWriteItem {
 #[gatt_server]
 struct Server {
     pin_service: PinService,
+    battery_service: BatteryService,
 }
 
 /// Pin service
 #[gatt_service(uuid = "a9c81b72-0f7a-4c59-b0a8-425e3bcf0a0e")]
 struct PinService {
-    #[characteristic(uuid = "13c0ef83-09bd-4767-97cb-ee46224ae6db", read)]
+    #[characteristic(uuid = "13c0ef83-09bd-4767-97cb-ee46224ae6db", read, notify)]
     pin_data_output: [u8; 32],
 
-    #[characteristic(uuid = "01037594-1bbb-4490-aa4d-f6d333b42e16", read)]
+    #[characteristic(uuid = "01037594-1bbb-4490-aa4d-f6d333b42e16", read, notify)]
     adc_data_output: [u8; 32],
 
     #[characteristic(uuid = "c79b2ca7-f39d-4060-8168-816fa26737b7", read, write)]
     pin_data_input: [u8; 32],
+
+    /// Start a BLE-driven OTA update: `[total_len: u32 LE]`. Write-only - must be written before
+    /// any `firmware_chunk` write, same as `POST /firmware/begin` over HTTP.
+    #[characteristic(uuid = "9a1d6b3e-2c9f-4a5b-8e7d-1f4a6c9d2b5e", write)]
+    firmware_begin: [u8; 4],
+
+    /// OTA image chunk: `[offset: u32 LE][len: u16 LE][data...]`. Write-only - progress is read
+    /// back via `firmware_status`.
+    #[characteristic(uuid = "6b77d16a-8db8-4db8-9f6a-6a6f1a9f9e7b", write)]
+    firmware_chunk: [u8; 128],
+
+    /// Finish a BLE-driven OTA update: verify length, mark the image updated, and reset. Write-only
+    /// and the value is ignored - any write triggers it, same as `POST /firmware/finish`.
+    #[characteristic(uuid = "4e7c1a9d-5b3e-4f6a-9d2c-8e1f6a3c9b5d", write)]
+    firmware_finish: [u8; 1],
+
+    /// OTA progress: `[state: u8][bytes_received: u32 LE]`.
+    #[characteristic(uuid = "3c6b2cf1-8c34-4c22-9f3e-2f9b6a2c7a6e", read)]
+    firmware_status: [u8; 5],
+
+    /// Persist a single `key=value` board config line, e.g. `bluetooth_name=rig-3` or
+    /// `pin25=pwm` - see `lib::config`. Write-only; takes effect on next boot.
+    #[characteristic(uuid = "8f2a9e4e-6f42-4b0a-9b8d-2a6b6f9b8e3a", write)]
+    config_write: [u8; 64],
+}
+
+/// Standard Bluetooth SIG Battery Service (0x180F), so centrals that already know how to read
+/// a conventional battery level don't need our custom pin service for that.
+#[gatt_service(uuid = "180f")]
+struct BatteryService {
+    /// Battery Level characteristic (0x2A19): percentage in the range 0..=100.
+    #[characteristic(uuid = "2a19", read, notify)]
+    battery_level: u8,
+}
+
+/// Build this board's BLE address from its factory eFuse MAC rather than a hardcoded value, so
+/// every unit in the field is unique. The two top bits of the last octet are forced to `11` to
+/// mark it as a static random address per the Core spec.
+fn device_address() -> Address {
+    let mut mac = esp_hal::efuse::Efuse::read_base_mac_address();
+    mac[5] |= 0xc0;
+    Address::random(mac)
+}
+
+/// Very rough ADC-counts-to-percentage mapping for a 2-cell-free LiPo supply read through an
+/// 11dB-attenuated ADC1 channel (0..=4095 over roughly 0..=3.3V at the pin). Treat anything at
+/// or above a "full" reading as 100% and anything at or below "empty" as 0%.
+const BATTERY_ADC_EMPTY: u32 = 2450; // ~3.0V
+const BATTERY_ADC_FULL: u32 = 3430; // ~4.2V
+
+fn adc_to_battery_percent(raw: u32) -> u8 {
+    let clamped = raw.clamp(BATTERY_ADC_EMPTY, BATTERY_ADC_FULL);
+    let range = BATTERY_ADC_FULL - BATTERY_ADC_EMPTY;
+    (((clamped - BATTERY_ADC_EMPTY) * 100) / range) as u8
+}
+
+/// Whether a connected central has subscribed (via the characteristic's CCCD) to
+/// `pin_data_output`/`adc_data_output` notifications. Gating on this avoids wasting BLE
+/// airtime sampling and notifying when nobody is listening.
+static PIN_OUTPUT_SUBSCRIBED: AtomicBool = AtomicBool::new(false);
+static ADC_OUTPUT_SUBSCRIBED: AtomicBool = AtomicBool::new(false);
+
+/// ADC readings below this delta from the last notified sample are treated as noise and
+/// suppressed rather than pushed out as a fresh notification.
+const ADC_NOTIFY_THRESHOLD: u32 = 20;
+
+fn cccd_requests_notify(value_bytes: &[u8]) -> bool {
+    value_bytes.first().is_some_and(|b| b & 0x01 != 0)
 }
 
 /// Run the BLE stack.
 ///
-pub async fn run<C>(controller: C, bluetooth_name: &str, adc_read_pin_nums: Vec<u8>)
-where
+pub async fn run<C>(
+    controller: C,
+    bluetooth_name: &str,
+    basic_pin_nums: Vec<u8>,
+    adc_read_pin_nums: Vec<u8>,
+    battery_pin: Option<u8>,
+) where
     C: Controller,
 {
-    // Using a fixed "random" address can be useful for testing. In real scenarios, one would
-    // use e.g. the MAC 6 byte array as the address (how to get that varies by the platform).
-    let address: Address = Address::random([0xff, 0x8f, 0x1a, 0x05, 0xe4, 0xff]);
+    let address = device_address();
     info!("Our address = {:?}", defmt::Debug2Format(&address));
 
     let mut resources: HostResources<DefaultPacketPool, CONNECTIONS_MAX, L2CAP_CHANNELS_MAX> =
@@ -82,7 +154,14 @@ where
                 Ok(conn) => {
                     // set up tasks when the connection is established to a central, so they don't run when no one is connected.
                     let a = gatt_events_task(&server, &conn);
-                    let b = custom_task(&server, &conn, &stack, adc_read_pin_nums.clone());
+                    let b = custom_task(
+                        &server,
+                        &conn,
+                        &stack,
+                        basic_pin_nums.clone(),
+                        adc_read_pin_nums.clone(),
+                        battery_pin,
+                    );
                     // run until any task ends (usually because the connection has been closed),
                     // then return to advertising state.
                     select(a, b).await;
@@ -132,6 +211,11 @@ async fn gatt_events_task<P: PacketPool>(
     let pin_data_output = server.pin_service.pin_data_output;
     let pin_data_input = server.pin_service.pin_data_input;
     let adc_data_output = server.pin_service.adc_data_output;
+    let firmware_begin = server.pin_service.firmware_begin;
+    let firmware_chunk = server.pin_service.firmware_chunk;
+    let firmware_finish = server.pin_service.firmware_finish;
+    let firmware_status = server.pin_service.firmware_status;
+    let config_write = server.pin_service.config_write;
     let reason = loop {
         match conn.next().await {
             GattConnectionEvent::Disconnected { reason } => break reason,
@@ -161,6 +245,12 @@ async fn gatt_events_task<P: PacketPool>(
                         } else if event.handle() == adc_data_output.handle {
                             let _value = server.get(&adc_data_output)?;
                             info!("[gatt] Read Event to ADC Data Output Characteristic");
+                        } else if event.handle() == firmware_status.handle {
+                            let mut status = [0u8; 5];
+                            status[0] = crate::ota::state() as u8;
+                            status[1..5].copy_from_slice(&crate::ota::bytes_received().to_le_bytes());
+                            server.set(&firmware_status, &status)?;
+                            info!("[gatt] Read Event to Firmware Status Characteristic: {:?}", status);
                         }
                     }
                     GattEvent::Write(event) => {
@@ -174,7 +264,68 @@ async fn gatt_events_task<P: PacketPool>(
                         info!("[gatt] Write Event data: {:?}", event.data());
                         let value = event.data();
                         let value_bytes: &[u8] = value.as_ref();
-                        if let Ok(str_value) = core::str::from_utf8(value_bytes) {
+
+                        if event.handle() == pin_data_output.cccd_handle {
+                            let subscribed = cccd_requests_notify(value_bytes);
+                            info!("[gatt] pin_data_output notify subscribed: {}", subscribed);
+                            PIN_OUTPUT_SUBSCRIBED.store(subscribed, Ordering::Relaxed);
+                        } else if event.handle() == adc_data_output.cccd_handle {
+                            let subscribed = cccd_requests_notify(value_bytes);
+                            info!("[gatt] adc_data_output notify subscribed: {}", subscribed);
+                            ADC_OUTPUT_SUBSCRIBED.store(subscribed, Ordering::Relaxed);
+                        } else if event.handle() == firmware_begin.handle {
+                            if value_bytes.len() < 4 {
+                                warn!("[gatt] firmware begin frame too short: {}", value_bytes.len());
+                            } else {
+                                let total_len = u32::from_le_bytes([
+                                    value_bytes[0],
+                                    value_bytes[1],
+                                    value_bytes[2],
+                                    value_bytes[3],
+                                ]) as usize;
+                                if crate::ota::begin(total_len).await.is_err() {
+                                    warn!("[gatt] failed to begin firmware update");
+                                }
+                            }
+                        } else if event.handle() == firmware_finish.handle {
+                            if crate::ota::finish().await.is_err() {
+                                warn!("[gatt] failed to finish firmware update");
+                            }
+                        } else if event.handle() == firmware_chunk.handle {
+                            if value_bytes.len() < 6 {
+                                warn!("[gatt] firmware chunk frame too short: {}", value_bytes.len());
+                            } else {
+                                let offset = u32::from_le_bytes([
+                                    value_bytes[0],
+                                    value_bytes[1],
+                                    value_bytes[2],
+                                    value_bytes[3],
+                                ]) as usize;
+                                let len = u16::from_le_bytes([value_bytes[4], value_bytes[5]]) as usize;
+                                let chunk = &value_bytes[6..];
+                                if len != chunk.len() {
+                                    warn!(
+                                        "[gatt] firmware chunk length header {} != payload {}",
+                                        len,
+                                        chunk.len()
+                                    );
+                                } else if crate::ota::write_chunk(offset, chunk).await.is_err() {
+                                    warn!("[gatt] firmware chunk write failed at offset {}", offset);
+                                }
+                            }
+                        } else if event.handle() == config_write.handle {
+                            let Ok(text) = core::str::from_utf8(value_bytes) else {
+                                warn!("[gatt] config write is not UTF-8");
+                                continue;
+                            };
+                            let Some((key, value)) = text.split_once('=') else {
+                                warn!("[gatt] config write missing '=': {}", text);
+                                continue;
+                            };
+                            if crate::config::set(key, value).is_err() {
+                                warn!("[gatt] failed to persist config {}={}", key, value);
+                            }
+                        } else if let Ok(str_value) = core::str::from_utf8(value_bytes) {
                             info!("[gatt] Write Event data as string: {}", str_value);
                             let Ok((pin_request, _len)) =
                                 serde_json_core::from_str::<PinRequest>(str_value)
@@ -261,36 +412,70 @@ async fn custom_task<C: Controller, P: PacketPool>(
     server: &Server<'_>,
     conn: &GattConnection<'_, '_, P>,
     stack: &Stack<'_, C, P>,
+    basic_pin_nums: Vec<u8>,
     adc_read_pin_nums: Vec<u8>,
+    battery_pin: Option<u8>,
 ) {
     let pin_data_output = server.pin_service.pin_data_output;
+    let mut last_adc_values: Vec<u32> = alloc::vec![0; adc_read_pin_nums.len()];
     loop {
-        let mut data = [0u8; 32];
-        let demo_data: &[u8] = &[3u8, 14, 100, 26, 100, 25, 100];
-        info!("[custom_task] demo_data length: {:?}", demo_data.len());
-        data[..demo_data.len()].copy_from_slice(demo_data);
-        if pin_data_output.notify(conn, &data).await.is_ok() {
-            info!("[custom_task] Notified connected central of pin data output");
+        if PIN_OUTPUT_SUBSCRIBED.load(Ordering::Relaxed) {
+            let num_pins = basic_pin_nums.len();
+            let mut pin_data: Vec<u8> = Vec::with_capacity(2 * num_pins + 1);
+            pin_data.push(num_pins as u8);
+            for pin_num in basic_pin_nums.iter().copied() {
+                pin_data.push(pin_num);
+                pin_data.push(crate::pin::load_state(pin_num) as u8);
+            }
+
+            let mut data = [0u8; 32];
+            let len = pin_data.len().min(data.len());
+            data[..len].copy_from_slice(&pin_data[..len]);
+            if pin_data_output.notify(conn, &data).await.is_ok() {
+                info!("[custom_task] Notified connected central of pin data output");
+            }
         }
 
-        let adc_data_output = server.pin_service.adc_data_output;
-        let num_pins = adc_read_pin_nums.len();
-        let mut demo_adc_data: Vec<u8> = Vec::with_capacity(3 * num_pins + 1);
-        demo_adc_data.push(num_pins as u8);
-        for pin_num in adc_read_pin_nums.clone() {
-            let value = match pin_num {
-                35 => crate::pin::GPIO35_STATE.load(Ordering::Relaxed),
-                32 => crate::pin::GPIO32_STATE.load(Ordering::Relaxed),
-                _ => 0,
-            };
-            let (high, low) = u32_to_u8_pair(value);
-            demo_adc_data.push(pin_num as u8);
-            demo_adc_data.push(high);
-            demo_adc_data.push(low);
+        if ADC_OUTPUT_SUBSCRIBED.load(Ordering::Relaxed) {
+            let adc_data_output = server.pin_service.adc_data_output;
+            let num_pins = adc_read_pin_nums.len();
+            let mut adc_data: Vec<u8> = Vec::with_capacity(3 * num_pins + 1);
+            adc_data.push(num_pins as u8);
+            let mut changed = false;
+            let mut values: Vec<u32> = Vec::with_capacity(num_pins);
+            for (idx, pin_num) in adc_read_pin_nums.iter().copied().enumerate() {
+                let value = crate::pin::load_state(pin_num);
+                if value.abs_diff(last_adc_values[idx]) >= ADC_NOTIFY_THRESHOLD {
+                    changed = true;
+                }
+                values.push(value);
+                let (high, low) = u32_to_u8_pair(value);
+                adc_data.push(pin_num);
+                adc_data.push(high);
+                adc_data.push(low);
+            }
+            if changed {
+                let mut data = [0u8; 32];
+                let len = adc_data.len().min(data.len());
+                data[..len].copy_from_slice(&adc_data[..len]);
+                if adc_data_output.notify(conn, &data).await.is_ok() {
+                    info!("[custom_task] Notified connected central of adc data output");
+                }
+                last_adc_values = values;
+            }
         }
-        data[..demo_adc_data.len()].copy_from_slice(demo_adc_data.as_slice());
-        if adc_data_output.notify(conn, &data).await.is_ok() {
-            info!("[custom_task] Notified connected central of adc data output");
+
+        // Only notify if a `battery_pin` was explicitly configured (see `crate::config`) - with
+        // none set there's no dedicated supply-sense input, and reporting a clamped 0%/whatever
+        // a general sensor pin happens to read would be misleading.
+        if let Some(battery_pin) = battery_pin {
+            let battery_level = server.battery_service.battery_level;
+            let percent = adc_to_battery_percent(crate::pin::load_state(battery_pin));
+            if server.set(&battery_level, &percent).is_ok()
+                && battery_level.notify(conn, &percent).await.is_ok()
+            {
+                info!("[custom_task] Notified connected central of battery level: {}", percent);
+            }
         }
 
         // read RSSI (Received Signal Strength Indicator) of the connection.
@@ -0,0 +1,190 @@
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use embedded_storage::{ReadStorage, Storage};
+use esp_storage::FlashStorage;
+
+/// Role a configured pin plays - drives which task loop (and which `PinRegistry::take_*` call)
+/// picks it up at boot, replacing the hardcoded `match pin_num` blocks this used to require.
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum PinRole {
+    Output,
+    Pwm,
+    Input,
+    Adc,
+}
+
+impl PinRole {
+    fn as_str(self) -> &'static str {
+        match self {
+            PinRole::Output => "output",
+            PinRole::Pwm => "pwm",
+            PinRole::Input => "input",
+            PinRole::Adc => "adc",
+        }
+    }
+
+    fn from_str(value: &str) -> Option<Self> {
+        match value {
+            "output" => Some(PinRole::Output),
+            "pwm" => Some(PinRole::Pwm),
+            "input" => Some(PinRole::Input),
+            "adc" => Some(PinRole::Adc),
+            _ => None,
+        }
+    }
+}
+
+/// Board configuration loaded from flash at boot.
+pub struct BoardConfig {
+    pub bluetooth_name: String,
+    pub pins: Vec<(u8, PinRole)>,
+    /// Per-pin low-pass cutoff frequency (Hz), from `pinNN_filter=lowpass:<hz>` lines. Only
+    /// meaningful for pins configured as `PinRole::Adc`; absent means no smoothing.
+    pub adc_filters: Vec<(u8, f32)>,
+    /// Which ADC channel (if any) is wired to a dedicated supply-voltage divider and should feed
+    /// the BLE Battery Service, from a `battery_pin=NN` line. `None` unless explicitly set -
+    /// there's no default, since on most boards no ADC pin is a dedicated battery sense input.
+    pub battery_pin: Option<u8>,
+    pub wifi_ssid: Option<String>,
+    pub wifi_pass: Option<String>,
+    /// Fixed address/subnet for the Wi-Fi and Ethernet links, e.g. `192.168.0.50/24`, from a
+    /// `static_ip=...` line. Only takes effect alongside `gateway_ip` - see
+    /// `wifi::NetMode::from_config`.
+    pub static_ip: Option<String>,
+    /// Gateway paired with `static_ip`, from a `gateway_ip=...` line.
+    pub gateway_ip: Option<String>,
+}
+
+/// Pin roles assumed when no `pinNN=...` line is present in flash - mirrors the board's
+/// original hardcoded layout (14/26/25/33 as outputs, 32/35 as ADC).
+const DEFAULT_PINS: [(u8, PinRole); 6] = [
+    (14, PinRole::Output),
+    (26, PinRole::Output),
+    (25, PinRole::Output),
+    (33, PinRole::Output),
+    (32, PinRole::Adc),
+    (35, PinRole::Adc),
+];
+
+const DEFAULT_BLUETOOTH_NAME: &str = "esp32-ble";
+
+/// Reserved flash region used as a tiny key=value store, laid out as `len: u32 LE` followed by
+/// `len` bytes of `key=value\n` lines.
+const CONFIG_FLASH_OFFSET: u32 = 0xA000;
+const CONFIG_MAX_LEN: usize = 512;
+
+fn read_raw() -> Option<String> {
+    let mut flash = FlashStorage::new();
+    let mut len_bytes = [0u8; 4];
+    flash.read(CONFIG_FLASH_OFFSET, &mut len_bytes).ok()?;
+    let len = u32::from_le_bytes(len_bytes) as usize;
+    if len == 0 || len > CONFIG_MAX_LEN {
+        return None;
+    }
+
+    let mut buf = [0u8; CONFIG_MAX_LEN];
+    flash.read(CONFIG_FLASH_OFFSET + 4, &mut buf[..len]).ok()?;
+    Some(String::from(core::str::from_utf8(&buf[..len]).ok()?))
+}
+
+fn write_raw(text: &str) -> Result<(), &'static str> {
+    if text.len() > CONFIG_MAX_LEN {
+        return Err("config too large to persist");
+    }
+
+    let mut flash = FlashStorage::new();
+    let len = text.len() as u32;
+    flash
+        .write(CONFIG_FLASH_OFFSET, &len.to_le_bytes())
+        .map_err(|_| "flash write failed")?;
+    flash
+        .write(CONFIG_FLASH_OFFSET + 4, text.as_bytes())
+        .map_err(|_| "flash write failed")?;
+    Ok(())
+}
+
+/// Load the board config from flash, defaulting any absent key to the board's original
+/// hardcoded layout.
+pub fn load() -> BoardConfig {
+    let text = read_raw().unwrap_or_default();
+
+    let mut bluetooth_name = None;
+    let mut pins = Vec::new();
+    let mut adc_filters = Vec::new();
+    let mut battery_pin = None;
+    let mut wifi_ssid = None;
+    let mut wifi_pass = None;
+    let mut static_ip = None;
+    let mut gateway_ip = None;
+
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if let Some(pin_num) = key.strip_prefix("pin").and_then(|rest| rest.strip_suffix("_filter")) {
+            if let (Ok(pin_num), Some(cutoff_hz)) = (
+                pin_num.parse::<u8>(),
+                value.strip_prefix("lowpass:").and_then(|hz| hz.parse::<f32>().ok()),
+            ) {
+                adc_filters.push((pin_num, cutoff_hz));
+            }
+        } else if let Some(pin_num) = key.strip_prefix("pin") {
+            if let (Ok(pin_num), Some(role)) = (pin_num.parse::<u8>(), PinRole::from_str(value)) {
+                pins.push((pin_num, role));
+            }
+        } else {
+            match key {
+                "bluetooth_name" => bluetooth_name = Some(String::from(value)),
+                "battery_pin" => battery_pin = value.parse::<u8>().ok(),
+                "wifi_ssid" => wifi_ssid = Some(String::from(value)),
+                "wifi_pass" => wifi_pass = Some(String::from(value)),
+                "static_ip" => static_ip = Some(String::from(value)),
+                "gateway_ip" => gateway_ip = Some(String::from(value)),
+                _ => {}
+            }
+        }
+    }
+
+    if pins.is_empty() {
+        pins.extend(DEFAULT_PINS);
+    }
+
+    BoardConfig {
+        bluetooth_name: bluetooth_name.unwrap_or_else(|| String::from(DEFAULT_BLUETOOTH_NAME)),
+        pins,
+        adc_filters,
+        battery_pin,
+        wifi_ssid,
+        wifi_pass,
+        static_ip,
+        gateway_ip,
+    }
+}
+
+/// Persist a single `key=value` line to flash, replacing any existing line for that key.
+pub fn set(key: &str, value: &str) -> Result<(), &'static str> {
+    let existing = read_raw().unwrap_or_default();
+    let prefix = format!("{}=", key);
+    let mut lines: Vec<String> = existing
+        .lines()
+        .filter(|line| !line.starts_with(prefix.as_str()))
+        .map(String::from)
+        .collect();
+    lines.push(format!("{}{}", prefix, value));
+    write_raw(&lines.join("\n"))
+}
+
+/// Persist a pin's role as a `pinNN=role` line, e.g. `pin25=pwm`.
+pub fn set_pin_role(pin_num: u8, role: PinRole) -> Result<(), &'static str> {
+    set(&format!("pin{}", pin_num), role.as_str())
+}
+
+/// Persist a pin's low-pass cutoff as a `pinNN_filter=lowpass:<hz>` line, e.g.
+/// `pin32_filter=lowpass:5`. Only takes effect while that pin is also configured as `Adc`.
+pub fn set_pin_filter(pin_num: u8, cutoff_hz: f32) -> Result<(), &'static str> {
+    set(&format!("pin{}_filter", pin_num), &format!("lowpass:{}", cutoff_hz))
+}
@@ -0,0 +1,64 @@
+/// Direct Form I second-order IIR ("biquad") filter coefficients:
+/// `y0 = b0*x0 + b1*x1 + b2*x2 - a1*y1 - a2*y2`.
+#[derive(Clone, Copy)]
+pub struct BiquadCoeffs {
+    pub b0: f32,
+    pub b1: f32,
+    pub b2: f32,
+    pub a1: f32,
+    pub a2: f32,
+}
+
+impl BiquadCoeffs {
+    /// No filtering - `y0 = x0`. Default for channels that don't opt into smoothing.
+    pub const PASS_THROUGH: BiquadCoeffs = BiquadCoeffs {
+        b0: 1.0,
+        b1: 0.0,
+        b2: 0.0,
+        a1: 0.0,
+        a2: 0.0,
+    };
+
+    /// A single-pole (first-order) RC low-pass, expressed as a biquad with `b2 = a2 = 0`, for a
+    /// given `cutoff_hz` sampled at `sample_rate_hz`.
+    pub fn single_pole_low_pass(cutoff_hz: f32, sample_rate_hz: f32) -> BiquadCoeffs {
+        let dt = 1.0 / sample_rate_hz;
+        let rc = 1.0 / (2.0 * core::f32::consts::PI * cutoff_hz);
+        let alpha = dt / (rc + dt);
+        BiquadCoeffs {
+            b0: alpha,
+            b1: 0.0,
+            b2: 0.0,
+            a1: -(1.0 - alpha),
+            a2: 0.0,
+        }
+    }
+}
+
+/// Per-channel Direct Form I filter state `{x1, x2, y1, y2}`. Zero-initialized, as required for
+/// a cold start.
+#[derive(Clone, Copy, Default)]
+pub struct BiquadState {
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl BiquadState {
+    /// Filter one new raw ADC sample through `coeffs`, then saturate and round the result back
+    /// into the ADC's valid `0..=4095` range.
+    pub fn apply(&mut self, coeffs: &BiquadCoeffs, x0: u32) -> u32 {
+        let x0 = x0 as f32;
+        let y0 = coeffs.b0 * x0 + coeffs.b1 * self.x1 + coeffs.b2 * self.x2
+            - coeffs.a1 * self.y1
+            - coeffs.a2 * self.y2;
+
+        self.x2 = self.x1;
+        self.x1 = x0;
+        self.y2 = self.y1;
+        self.y1 = y0;
+
+        y0.round().clamp(0.0, 4095.0) as u32
+    }
+}
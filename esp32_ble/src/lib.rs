@@ -0,0 +1,34 @@
+#![no_std]
+
+pub mod ble;
+pub mod config;
+pub mod filter;
+pub mod ota;
+pub mod pin;
+
+// These live under `esp32_wifi/` rather than alongside the rest of this crate's sources - the
+// two directories build as one crate, split by transport (BLE vs. Wi-Fi/Ethernet) rather than
+// by Cargo package.
+#[path = "../../esp32_wifi/src/esp_now.rs"]
+pub mod esp_now;
+#[path = "../../esp32_wifi/src/ethernet.rs"]
+pub mod ethernet;
+#[path = "../../esp32_wifi/src/provisioning.rs"]
+pub mod provisioning;
+#[path = "../../esp32_wifi/src/web.rs"]
+pub mod web;
+#[path = "../../esp32_wifi/src/wifi.rs"]
+pub mod wifi;
+
+/// Forces a `T` into a `&'static mut T` by handing it to a `StaticCell`. Used throughout for
+/// embassy resources (the radio controller, stack resources, timers, ...) that tasks need to borrow for
+/// `'static`.
+#[macro_export]
+macro_rules! mk_static {
+    ($t:ty, $val:expr) => {{
+        static STATIC_CELL: static_cell::StaticCell<$t> = static_cell::StaticCell::new();
+        #[deny(unused_attributes)]
+        let x = STATIC_CELL.uninit().write($val);
+        x
+    }};
+}
@@ -0,0 +1,142 @@
+use core::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+
+use defmt::{info, warn};
+use embassy_embedded_hal::adapter::BlockingAsync;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_boot::FirmwareUpdater;
+use esp_storage::FlashStorage;
+
+/// State of an in-progress (or finished) OTA update, reported back to whichever transport
+/// (HTTP or BLE) is driving it.
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum OtaState {
+    Idle = 0,
+    Receiving = 1,
+    Verifying = 2,
+    Done = 3,
+    Error = 4,
+}
+
+impl OtaState {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => OtaState::Receiving,
+            2 => OtaState::Verifying,
+            3 => OtaState::Done,
+            4 => OtaState::Error,
+            _ => OtaState::Idle,
+        }
+    }
+}
+
+static STATE: AtomicU8 = AtomicU8::new(OtaState::Idle as u8);
+static BYTES_RECEIVED: AtomicU32 = AtomicU32::new(0);
+
+pub fn state() -> OtaState {
+    OtaState::from_u8(STATE.load(Ordering::Relaxed))
+}
+
+pub fn bytes_received() -> u32 {
+    BYTES_RECEIVED.load(Ordering::Relaxed)
+}
+
+fn set_state(state: OtaState) {
+    STATE.store(state as u8, Ordering::Relaxed);
+}
+
+type Flash = BlockingAsync<FlashStorage>;
+
+/// Serializes OTA writes across the HTTP and BLE update paths - only one update can be in
+/// flight at a time, and chunks must land in order.
+static UPDATER: Mutex<NoopRawMutex, Option<OtaSession>> = Mutex::new(None);
+
+struct OtaSession {
+    updater: FirmwareUpdater<'static, Flash, Flash>,
+    expected_len: usize,
+}
+
+/// Begin a new OTA update of `total_len` bytes: erases the secondary (DFU) partition once and
+/// resets the received-byte counter. Returns an error if an update is already in progress.
+pub async fn begin(total_len: usize) -> Result<(), &'static str> {
+    let mut guard = UPDATER.lock().await;
+    if guard.is_some() {
+        return Err("update already in progress");
+    }
+
+    let dfu = Flash::new(FlashStorage::new());
+    let state_flash = Flash::new(FlashStorage::new());
+    let mut updater = FirmwareUpdater::default(dfu, state_flash);
+
+    set_state(OtaState::Receiving);
+    BYTES_RECEIVED.store(0, Ordering::Relaxed);
+
+    if let Err(e) = updater.prepare_update().await {
+        warn!("[ota] failed to erase DFU partition: {:?}", defmt::Debug2Format(&e));
+        set_state(OtaState::Error);
+        return Err("failed to erase DFU partition");
+    }
+
+    *guard = Some(OtaSession {
+        updater,
+        expected_len: total_len,
+    });
+    info!("[ota] update started, expecting {} bytes", total_len);
+    Ok(())
+}
+
+/// Write the next sequential chunk of the incoming image at `offset`. Rejected unless `offset`
+/// is where the previous chunk ended.
+pub async fn write_chunk(offset: usize, data: &[u8]) -> Result<(), &'static str> {
+    let mut guard = UPDATER.lock().await;
+    let Some(session) = guard.as_mut() else {
+        return Err("no update in progress");
+    };
+
+    if offset != bytes_received() as usize {
+        warn!("[ota] out-of-order chunk at offset {}, expected {}", offset, bytes_received());
+        return Err("chunk out of order");
+    }
+
+    if let Err(e) = session.updater.write_firmware(offset, data).await {
+        warn!("[ota] write failed at offset {}: {:?}", offset, defmt::Debug2Format(&e));
+        set_state(OtaState::Error);
+        *guard = None;
+        return Err("flash write failed");
+    }
+
+    BYTES_RECEIVED.store((offset + data.len()) as u32, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Finish the update: verify the total length matches what was declared in `begin`, mark the
+/// new image as ready to boot, and reset.
+pub async fn finish() -> Result<(), &'static str> {
+    let mut guard = UPDATER.lock().await;
+    let Some(mut session) = guard.take() else {
+        return Err("no update in progress");
+    };
+
+    if bytes_received() as usize != session.expected_len {
+        warn!(
+            "[ota] length mismatch: received {} expected {}",
+            bytes_received(),
+            session.expected_len
+        );
+        set_state(OtaState::Error);
+        return Err("received length does not match declared length");
+    }
+
+    set_state(OtaState::Verifying);
+    let mut aligned_buf = [0u8; 4096];
+    if let Err(e) = session.updater.mark_updated(&mut aligned_buf).await {
+        warn!("[ota] mark_updated failed: {:?}", defmt::Debug2Format(&e));
+        set_state(OtaState::Error);
+        return Err("failed to mark image as updated");
+    }
+
+    set_state(OtaState::Done);
+    info!("[ota] update complete, resetting");
+    esp_hal::system::software_reset();
+}
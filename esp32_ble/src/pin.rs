@@ -7,20 +7,136 @@ use embedded_hal::pwm::SetDutyCycle;
 use esp_hal::analog::adc::Adc;
 use esp_hal::analog::adc::AdcConfig;
 use esp_hal::analog::adc::AdcPin;
+use esp_hal::analog::adc::Attenuation;
 use esp_hal::gpio::Input;
+use esp_hal::gpio::InputConfig;
+use esp_hal::gpio::OutputConfig;
 use esp_hal::gpio::{Level, Output};
 use esp_hal::ledc::HighSpeed;
 use esp_hal::ledc::channel::{Channel, ChannelIFace};
 use esp_hal::peripherals::ADC1;
+use esp_hal::peripherals::GPIO14;
+use esp_hal::peripherals::GPIO25;
+use esp_hal::peripherals::GPIO26;
 use esp_hal::peripherals::GPIO32;
+use esp_hal::peripherals::GPIO33;
 use esp_hal::peripherals::GPIO35;
 
+use crate::filter::{BiquadCoeffs, BiquadState};
+
+/// Owns every pin the board knows how to use, taken from `Peripherals` exactly once at boot.
+/// `take_output`/`take_input`/`take_adc` each hand a pin out by number, so the config-driven
+/// loops in `main` no longer need their own `match pin_num { 14 => ..., 26 => ... }` blocks and
+/// can't accidentally take the same pin twice across categories.
+pub struct PinRegistry {
+    gpio14: Option<GPIO14<'static>>,
+    gpio26: Option<GPIO26<'static>>,
+    gpio25: Option<GPIO25<'static>>,
+    gpio33: Option<GPIO33<'static>>,
+    gpio32: Option<GPIO32<'static>>,
+    gpio35: Option<GPIO35<'static>>,
+}
+
+impl PinRegistry {
+    pub fn new(
+        gpio14: GPIO14<'static>,
+        gpio26: GPIO26<'static>,
+        gpio25: GPIO25<'static>,
+        gpio33: GPIO33<'static>,
+        gpio32: GPIO32<'static>,
+        gpio35: GPIO35<'static>,
+    ) -> Self {
+        Self {
+            gpio14: Some(gpio14),
+            gpio26: Some(gpio26),
+            gpio25: Some(gpio25),
+            gpio33: Some(gpio33),
+            gpio32: Some(gpio32),
+            gpio35: Some(gpio35),
+        }
+    }
+
+    /// Take `pin_num` as a digital output, or `None` if it isn't output-capable or was already
+    /// taken (by this or another category).
+    pub fn take_output(&mut self, pin_num: u8) -> Option<Output<'static>> {
+        let config = OutputConfig::default();
+        match pin_num {
+            14 => self.gpio14.take().map(|p| Output::new(p, Level::Low, config)),
+            26 => self.gpio26.take().map(|p| Output::new(p, Level::Low, config)),
+            25 => self.gpio25.take().map(|p| Output::new(p, Level::Low, config)),
+            33 => self.gpio33.take().map(|p| Output::new(p, Level::Low, config)),
+            _ => None,
+        }
+    }
+
+    /// Take `pin_num` as a digital input, or `None` if it isn't input-capable or was already
+    /// taken.
+    pub fn take_input(&mut self, pin_num: u8) -> Option<Input<'static>> {
+        let config = InputConfig::default();
+        match pin_num {
+            14 => self.gpio14.take().map(|p| Input::new(p, config)),
+            26 => self.gpio26.take().map(|p| Input::new(p, config)),
+            25 => self.gpio25.take().map(|p| Input::new(p, config)),
+            33 => self.gpio33.take().map(|p| Input::new(p, config)),
+            _ => None,
+        }
+    }
+
+    /// Take `pin_num` as an ADC input, enabling it on `adc_config` and wrapping it in an
+    /// `AdcReadPinTaskItem` ready to hand to `adc_read_pin_task`. `filter_coeffs` is whatever the
+    /// caller wants applied to this channel - pass `BiquadCoeffs::PASS_THROUGH` for none, or
+    /// `BiquadCoeffs::single_pole_low_pass(cutoff_hz, ADC_SAMPLE_RATE_HZ)` per `crate::config`'s
+    /// `pinNN_filter=lowpass:<hz>` key.
+    pub fn take_adc(
+        &mut self,
+        pin_num: u8,
+        adc_config: &mut AdcConfig<ADC1<'static>>,
+        filter_coeffs: BiquadCoeffs,
+    ) -> Option<AdcReadPinTaskItem> {
+        match pin_num {
+            32 => self.gpio32.take().map(|p| AdcReadPinTaskItem {
+                pin_num,
+                gpio32: Some(adc_config.enable_pin(p, Attenuation::_11dB)),
+                gpio35: None,
+                filter_coeffs,
+                filter_state: BiquadState::default(),
+            }),
+            35 => self.gpio35.take().map(|p| AdcReadPinTaskItem {
+                pin_num,
+                gpio32: None,
+                gpio35: Some(adc_config.enable_pin(p, Attenuation::_11dB)),
+                filter_coeffs,
+                filter_state: BiquadState::default(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Sample rate `adc_read_pin_task` actually runs at (it polls once per 500ms `Timer`), needed to
+/// turn a `pinNN_filter=lowpass:<hz>` cutoff into biquad coefficients.
+pub const ADC_SAMPLE_RATE_HZ: f32 = 2.0;
+
 pub static GPIO14_STATE: AtomicU32 = AtomicU32::new(0);
 pub static GPIO26_STATE: AtomicU32 = AtomicU32::new(0);
 pub static GPIO25_STATE: AtomicU32 = AtomicU32::new(0);
 pub static GPIO32_STATE: AtomicU32 = AtomicU32::new(0);
 pub static GPIO35_STATE: AtomicU32 = AtomicU32::new(0);
 pub static GPIO33_STATE: AtomicU32 = AtomicU32::new(0);
+
+/// Read the live state of `pin_num`'s shared atomic, or `0` if it's not a known pin.
+pub fn load_state(pin_num: u8) -> u32 {
+    match pin_num {
+        14 => GPIO14_STATE.load(Ordering::Relaxed),
+        26 => GPIO26_STATE.load(Ordering::Relaxed),
+        25 => GPIO25_STATE.load(Ordering::Relaxed),
+        33 => GPIO33_STATE.load(Ordering::Relaxed),
+        32 => GPIO32_STATE.load(Ordering::Relaxed),
+        35 => GPIO35_STATE.load(Ordering::Relaxed),
+        _ => 0,
+    }
+}
+
 pub struct BasicWritePinTaskItem {
     pub pin_num: u8,
     pub pin: Output<'static>,
@@ -107,6 +223,10 @@ pub struct AdcReadPinTaskItem {
     pub pin_num: u8,
     pub gpio35: Option<AdcPin<GPIO35<'static>, ADC1<'static>>>,
     pub gpio32: Option<AdcPin<GPIO32<'static>, ADC1<'static>>>,
+    /// Biquad smoothing applied to raw samples before they're stored - defaults to
+    /// `BiquadCoeffs::PASS_THROUGH`, so existing behavior is unchanged unless overridden.
+    pub filter_coeffs: BiquadCoeffs,
+    pub filter_state: BiquadState,
 }
 
 #[embassy_executor::task]
@@ -151,10 +271,11 @@ pub async fn adc_read_pin_task(
                 }
                 _ => 0,
             };
+            let filtered = item.filter_state.apply(&item.filter_coeffs, state);
 
             match item.pin_num {
-                35 => GPIO35_STATE.store(state, Ordering::Relaxed),
-                32 => GPIO32_STATE.store(state, Ordering::Relaxed),
+                35 => GPIO35_STATE.store(filtered, Ordering::Relaxed),
+                32 => GPIO32_STATE.store(filtered, Ordering::Relaxed),
                 _ => {}
             }
         }
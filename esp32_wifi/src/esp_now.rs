@@ -0,0 +1,109 @@
+extern crate alloc;
+
+use core::sync::atomic::Ordering;
+
+use alloc::vec::Vec;
+use embassy_futures::select::{Either, select};
+use embassy_time::{Duration, Ticker};
+use esp_println::println;
+use esp_radio::esp_now::{BROADCAST_ADDRESS, EspNow, PeerInfo};
+
+/// Op codes for the 3-byte `[pin_num, op, value]` command frame sent between peers.
+const OP_BASIC_WRITE: u8 = 0;
+const OP_PWM_WRITE: u8 = 1;
+
+/// Parse a colon-separated MAC address like `"AA:BB:CC:DD:EE:FF"`, as used by `ESP_NOW_PEER`.
+pub fn parse_mac(s: &str) -> Option<[u8; 6]> {
+    let mut mac = [0u8; 6];
+    let mut bytes = s.split(':');
+    for byte in mac.iter_mut() {
+        *byte = u8::from_str_radix(bytes.next()?, 16).ok()?;
+    }
+    if bytes.next().is_some() {
+        return None;
+    }
+    Some(mac)
+}
+
+/// Initialize ESP-NOW on the shared radio controller and register a broadcast peer so any
+/// paired board on the same channel can be commanded without an AP.
+///
+/// ESP-NOW and `crate::wifi::start_wifi` both need exclusive ownership of the `WIFI` peripheral,
+/// so a board runs one or the other - see the `ESP_NOW_PEER` env var branch in `main`.
+pub fn init(radio_init: &esp_radio::Controller<'static>, wifi: esp_hal::peripherals::WIFI<'static>) -> EspNow<'static> {
+    let mut esp_now = esp_radio::esp_now::EspNow::new(radio_init, wifi).expect("Failed to initialize ESP-NOW");
+
+    esp_now
+        .add_peer(PeerInfo {
+            peer_address: BROADCAST_ADDRESS,
+            lmk: None,
+            channel: None,
+            encrypt: false,
+        })
+        .ok();
+
+    esp_now
+}
+
+fn store_pin_state(pin_num: u8, state: u32) {
+    match pin_num {
+        14 => crate::pin::GPIO14_STATE.store(state, Ordering::Relaxed),
+        26 => crate::pin::GPIO26_STATE.store(state, Ordering::Relaxed),
+        25 => crate::pin::GPIO25_STATE.store(state, Ordering::Relaxed),
+        33 => crate::pin::GPIO33_STATE.store(state, Ordering::Relaxed),
+        _ => {}
+    }
+}
+
+/// Owns the single `EspNow<'static>` handle and drives both directions of the link: decode
+/// incoming `[pin_num, op, value]` command frames and apply them to the same `GPIOxx_STATE`
+/// atomics the basic-write/PWM-write tasks poll, and periodically push this board's ADC samples
+/// to `peer`. A frame can't be split across two tasks (there's only one `EspNow` handle), so
+/// this races the next receive against a fixed 500ms send tick with `select` - a stream of
+/// incoming commands can't postpone the next tick, and a receive waits at most one round of
+/// ADC sends.
+#[embassy_executor::task]
+pub async fn esp_now_task(mut esp_now: EspNow<'static>, peer: [u8; 6], adc_read_pin_nums: Vec<u8>) {
+    // Unicast sends only go out to a registered peer.
+    esp_now
+        .add_peer(PeerInfo {
+            peer_address: peer,
+            lmk: None,
+            channel: None,
+            encrypt: false,
+        })
+        .ok();
+    let mut send_ticker = Ticker::every(Duration::from_millis(500));
+    loop {
+        match select(esp_now.receive_async(), send_ticker.next()).await {
+            Either::First(received) => {
+                let data = received.data();
+                if data.len() != 3 {
+                    println!("[esp-now] ignoring malformed frame, len={}", data.len());
+                    continue;
+                }
+
+                let pin_num = data[0];
+                let op = data[1];
+                let value = data[2];
+                match op {
+                    OP_BASIC_WRITE | OP_PWM_WRITE => store_pin_state(pin_num, value as u32),
+                    _ => println!("[esp-now] unknown op {}", op),
+                }
+            }
+            Either::Second(()) => {
+                for pin_num in adc_read_pin_nums.iter().copied() {
+                    let value = match pin_num {
+                        35 => crate::pin::GPIO35_STATE.load(Ordering::Relaxed),
+                        32 => crate::pin::GPIO32_STATE.load(Ordering::Relaxed),
+                        _ => continue,
+                    };
+                    let frame = [pin_num, (value >> 8) as u8, value as u8];
+                    if esp_now.send_async(&peer, &frame).await.is_err() {
+                        println!("[esp-now] failed to send ADC sample for pin {}", pin_num);
+                    }
+                }
+            }
+        }
+    }
+}
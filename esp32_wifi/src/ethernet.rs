@@ -0,0 +1,90 @@
+use embassy_executor::Spawner;
+use embassy_net::{Stack, StackResources, StaticConfigV4};
+use embassy_net_wiznet::chip::W5500;
+use embassy_net_wiznet::{Device, Runner, State};
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::spi::SpiDevice;
+use esp_hal::gpio::Output;
+use esp_println::println;
+
+use crate::mk_static;
+use crate::wifi::NetMode;
+
+/// Spawn the WIZnet W5500 link/runner tasks and bring up an `embassy_net::Stack` over them, so
+/// `web_task` can be run against wired Ethernet the same way it's run against the Wi-Fi stack in
+/// `wifi::start_wifi` - same `Application` router, same handlers, different link.
+pub async fn start_ethernet<SPI, INT>(
+    mac_addr: [u8; 6],
+    spi_device: SPI,
+    int_pin: INT,
+    reset_pin: Output<'static>,
+    net_seed: u64,
+    spawner: &Spawner,
+    net_mode: NetMode,
+) -> Stack<'static>
+where
+    SPI: SpiDevice + 'static,
+    INT: Wait + 'static,
+{
+    let state = mk_static!(State<8, 8>, State::<8, 8>::new());
+    let (device, runner) = embassy_net_wiznet::new(mac_addr, spi_device, int_pin, reset_pin, state)
+        .await
+        .expect("Failed to initialize W5500");
+    spawner.must_spawn(eth_runner_task(runner));
+
+    let net_config = match net_mode {
+        NetMode::Static { cidr, gateway } => embassy_net::Config::ipv4_static(StaticConfigV4 {
+            address: cidr,
+            gateway: Some(gateway),
+            dns_servers: Default::default(),
+        }),
+        NetMode::Dhcp => embassy_net::Config::dhcpv4(Default::default()),
+    };
+
+    let (stack, net_runner) = embassy_net::new(
+        device,
+        net_config,
+        mk_static!(StackResources<3>, StackResources::<3>::new()),
+        net_seed,
+    );
+    spawner.must_spawn(eth_net_task(net_runner));
+
+    wait_for_link(stack).await;
+    stack
+}
+
+#[embassy_executor::task]
+async fn eth_runner_task<SPI, INT>(
+    runner: Runner<'static, W5500, SPI, INT, Output<'static>>,
+) -> !
+where
+    SPI: SpiDevice + 'static,
+    INT: Wait + 'static,
+{
+    runner.run().await
+}
+
+#[embassy_executor::task]
+async fn eth_net_task(mut runner: embassy_net::Runner<'static, Device<'static>>) -> ! {
+    runner.run().await
+}
+
+async fn wait_for_link(stack: Stack<'_>) {
+    println!("[ethernet] waiting for link to be up");
+    loop {
+        if stack.is_link_up() {
+            break;
+        }
+        Timer::after(Duration::from_millis(500)).await;
+    }
+
+    println!("[ethernet] waiting to get IP address (and lease, if DHCP)...");
+    loop {
+        if let Some(config) = stack.config_v4() {
+            println!("[ethernet] got IP: {}", config.address);
+            break;
+        }
+        Timer::after(Duration::from_millis(500)).await;
+    }
+}
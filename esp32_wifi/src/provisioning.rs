@@ -0,0 +1,158 @@
+extern crate alloc;
+
+use alloc::string::String;
+
+use core::net::Ipv4Addr;
+
+use embassy_executor::Spawner;
+use embassy_net::{Ipv4Cidr, Stack, StackResources};
+use embassy_time::{Duration, Timer};
+use esp_hal::rng::Rng;
+use esp_println::println;
+use esp_radio::wifi::{AccessPointConfig, ClientConfig, ModeConfig, WifiController};
+use picoserve::{AppBuilder, AppRouter, Router, response::IntoResponse, routing};
+
+use crate::mk_static;
+
+/// SSID the board advertises while waiting to be provisioned.
+const PROVISIONING_SSID: &str = "esp32-setup";
+/// Gateway/address of the board while it's acting as the provisioning AP.
+fn provisioning_gateway() -> Ipv4Cidr {
+    Ipv4Cidr::new(Ipv4Addr::new(192, 168, 4, 1), 24)
+}
+
+#[derive(serde::Deserialize)]
+struct ProvisionRequest {
+    ssid: String,
+    password: String,
+}
+
+#[derive(serde::Serialize)]
+struct ProvisionResponse {
+    success: bool,
+}
+
+pub struct ProvisioningApp;
+
+impl AppBuilder for ProvisioningApp {
+    type PathRouter = impl routing::PathRouter;
+
+    fn build_app(self) -> picoserve::Router<Self::PathRouter> {
+        picoserve::Router::new().route("/provision", routing::post(provision_handler))
+    }
+}
+
+static PROVISIONED: core::sync::atomic::AtomicBool = core::sync::atomic::AtomicBool::new(false);
+
+async fn provision_handler(
+    input: picoserve::extract::Json<ProvisionRequest>,
+) -> impl IntoResponse {
+    save_credentials(&input.0.ssid, &input.0.password);
+    PROVISIONED.store(true, core::sync::atomic::Ordering::Relaxed);
+    picoserve::response::Json(ProvisionResponse { success: true })
+}
+
+/// Read stored Wi-Fi credentials back out of the shared flash-persisted board config, if any
+/// were saved.
+pub fn load_credentials() -> Option<(String, String)> {
+    let config = crate::config::load();
+    Some((config.wifi_ssid?, config.wifi_pass?))
+}
+
+fn save_credentials(ssid: &str, password: &str) {
+    if crate::config::set("wifi_ssid", ssid).is_err()
+        || crate::config::set("wifi_pass", password).is_err()
+    {
+        println!("[provisioning] failed to persist credentials");
+    }
+}
+
+/// Put the controller into SoftAP mode, serve a tiny provisioning endpoint at the gateway
+/// address, and block until a client has POSTed `{ssid, password}` to `/provision`. The
+/// credentials are persisted to flash; the caller switches the same controller back to
+/// client mode afterwards.
+async fn run_provisioning_ap(
+    wifi_controller: &mut WifiController<'static>,
+    ap_interface: esp_radio::wifi::WifiDevice<'static>,
+    rng: Rng,
+    spawner: &Spawner,
+) {
+    let ap_config = ModeConfig::Access(
+        AccessPointConfig::default().with_ssid(PROVISIONING_SSID.into()),
+    );
+    wifi_controller.set_config(&ap_config).unwrap();
+    wifi_controller.start_async().await.unwrap();
+    println!("[provisioning] SoftAP '{}' started", PROVISIONING_SSID);
+
+    let net_seed = rng.random() as u64 | ((rng.random() as u64) << 32);
+    let net_config = embassy_net::Config::ipv4_static(embassy_net::StaticConfigV4 {
+        address: provisioning_gateway(),
+        gateway: None,
+        dns_servers: Default::default(),
+    });
+    let (stack, runner) = embassy_net::new(
+        ap_interface,
+        net_config,
+        mk_static!(StackResources<3>, StackResources::<3>::new()),
+        net_seed,
+    );
+    spawner.must_spawn(ap_net_task(runner));
+
+    let router = picoserve::make_static!(AppRouter<ProvisioningApp>, ProvisioningApp.build_app());
+    let config = picoserve::make_static!(
+        picoserve::Config<Duration>,
+        picoserve::Config::new(picoserve::Timeouts {
+            start_read_request: Some(Duration::from_secs(5)),
+            read_request: Some(Duration::from_secs(1)),
+            write: Some(Duration::from_secs(1)),
+            persistent_start_read_request: Some(Duration::from_secs(1)),
+        })
+        .keep_connection_alive()
+    );
+    spawner.must_spawn(provisioning_web_task(stack, router, config));
+
+    println!("[provisioning] waiting for credentials at http://192.168.4.1/provision");
+    while !PROVISIONED.load(core::sync::atomic::Ordering::Relaxed) {
+        Timer::after(Duration::from_millis(500)).await;
+    }
+}
+
+#[embassy_executor::task]
+async fn ap_net_task(mut runner: embassy_net::Runner<'static, esp_radio::wifi::WifiDevice<'static>>) {
+    runner.run().await
+}
+
+#[embassy_executor::task]
+async fn provisioning_web_task(
+    stack: Stack<'static>,
+    router: &'static AppRouter<ProvisioningApp>,
+    config: &'static picoserve::Config<Duration>,
+) -> ! {
+    let mut tcp_rx_buffer = [0; 1024];
+    let mut tcp_tx_buffer = [0; 1024];
+    let mut http_buffer = [0; 2048];
+
+    picoserve::Server::new(router, config, &mut http_buffer)
+        .listen_and_serve(0, stack, 80, &mut tcp_rx_buffer, &mut tcp_tx_buffer)
+        .await
+        .into_never()
+}
+
+/// Resolve the SSID/password `start_wifi` should connect with: use the credentials stored in
+/// flash if present, otherwise run the SoftAP provisioning flow on the same controller until a
+/// client supplies them, persist them, and fall through to the normal STA connection path.
+pub async fn provision_if_needed(
+    wifi_controller: &mut WifiController<'static>,
+    ap_interface: esp_radio::wifi::WifiDevice<'static>,
+    rng: Rng,
+    spawner: &Spawner,
+) -> (String, String) {
+    if let Some(creds) = load_credentials() {
+        return creds;
+    }
+
+    run_provisioning_ap(wifi_controller, ap_interface, rng, spawner).await;
+    wifi_controller.stop_async().await.ok();
+
+    load_credentials().expect("credentials were just persisted by the provisioning handler")
+}
@@ -1,5 +1,6 @@
 extern crate alloc;
 
+use alloc::format;
 use alloc::vec::Vec;
 use core::sync::atomic::Ordering;
 
@@ -41,6 +42,73 @@ struct PinReadResponse {
     success: bool,
 }
 
+#[derive(serde::Serialize)]
+struct PinSnapshot {
+    pin_num: u8,
+    state: u32,
+}
+
+#[derive(serde::Serialize)]
+struct PinsResponse {
+    pins: Vec<PinSnapshot>,
+}
+
+#[derive(serde::Deserialize)]
+struct WritePinQuery {
+    level: Option<heapless::String<4>>,
+    duty: Option<u8>,
+}
+
+#[derive(serde::Deserialize)]
+struct FirmwareBeginRequest {
+    total_len: usize,
+}
+
+#[derive(serde::Deserialize)]
+struct FirmwareChunkRequest {
+    offset: usize,
+    data: Vec<u8>,
+}
+
+#[derive(serde::Serialize)]
+struct FirmwareResponse {
+    success: bool,
+}
+
+#[derive(serde::Serialize)]
+struct FirmwareStatusResponse {
+    state: u8,
+    bytes_received: u32,
+}
+
+#[derive(serde::Deserialize)]
+struct PinRoleUpdate {
+    pin_num: u8,
+    role: heapless::String<8>,
+    /// Low-pass cutoff in Hz, only meaningful while `role` is `"adc"`. Omit to leave whatever
+    /// filter (if any) is already persisted for this pin untouched.
+    filter_lowpass_hz: Option<f32>,
+}
+
+/// Fields are all optional so a caller can update just the pieces they care about; anything
+/// omitted keeps whatever is already persisted to flash.
+#[derive(serde::Deserialize)]
+struct ConfigUpdateRequest {
+    bluetooth_name: Option<heapless::String<32>>,
+    wifi_ssid: Option<heapless::String<32>>,
+    wifi_pass: Option<heapless::String<64>>,
+    /// `192.168.0.50/24`-style static address for the Wi-Fi/Ethernet links. Only takes effect
+    /// alongside `gateway_ip` - see `wifi::NetMode::from_config`.
+    static_ip: Option<heapless::String<24>>,
+    gateway_ip: Option<heapless::String<16>>,
+    pins: Option<Vec<PinRoleUpdate>>,
+}
+
+#[derive(serde::Serialize)]
+struct ConfigResponse {
+    success: bool,
+}
+
 pub struct Application;
 
 impl AppBuilder for Application {
@@ -50,6 +118,168 @@ impl AppBuilder for Application {
         picoserve::Router::new()
             .route("/write-pins", routing::post(write_pins_handler))
             .route("/read-pins", routing::post(read_pins_handler))
+            .route("/pins", routing::get(pins_handler))
+            .route(
+                "/pin/{pin_num}",
+                routing::get(read_pin_handler).post(write_pin_handler),
+            )
+            .route("/firmware/begin", routing::post(firmware_begin_handler))
+            .route("/firmware/chunk", routing::post(firmware_chunk_handler))
+            .route("/firmware/finish", routing::post(firmware_finish_handler))
+            .route("/firmware/status", routing::get(firmware_status_handler))
+            .route("/config", routing::post(config_handler))
+    }
+}
+
+/// `POST /config` - persist board configuration (pin roles, BLE name, Wi-Fi credentials, static
+/// IP/gateway) to flash. Fields left out of the request body are left untouched. Takes effect on
+/// next boot.
+async fn config_handler(input: picoserve::extract::Json<ConfigUpdateRequest>) -> impl IntoResponse {
+    let mut success = true;
+
+    if let Some(name) = &input.0.bluetooth_name {
+        success &= crate::config::set("bluetooth_name", name.as_str()).is_ok();
+    }
+    if let Some(ssid) = &input.0.wifi_ssid {
+        success &= crate::config::set("wifi_ssid", ssid.as_str()).is_ok();
+    }
+    if let Some(pass) = &input.0.wifi_pass {
+        success &= crate::config::set("wifi_pass", pass.as_str()).is_ok();
+    }
+    if let Some(static_ip) = &input.0.static_ip {
+        success &= crate::config::set("static_ip", static_ip.as_str()).is_ok();
+    }
+    if let Some(gateway_ip) = &input.0.gateway_ip {
+        success &= crate::config::set("gateway_ip", gateway_ip.as_str()).is_ok();
+    }
+    if let Some(pins) = &input.0.pins {
+        for pin in pins {
+            success &= crate::config::set(&format!("pin{}", pin.pin_num), pin.role.as_str()).is_ok();
+            if let Some(cutoff_hz) = pin.filter_lowpass_hz {
+                success &= crate::config::set_pin_filter(pin.pin_num, cutoff_hz).is_ok();
+            }
+        }
+    }
+
+    picoserve::response::Json(ConfigResponse { success })
+}
+
+/// `POST /firmware/begin` - declare the total image size and erase the secondary partition.
+async fn firmware_begin_handler(
+    input: picoserve::extract::Json<FirmwareBeginRequest>,
+) -> impl IntoResponse {
+    let success = crate::ota::begin(input.0.total_len).await.is_ok();
+    picoserve::response::Json(FirmwareResponse { success })
+}
+
+/// `POST /firmware/chunk` - write the next sequential slice of the image.
+async fn firmware_chunk_handler(
+    input: picoserve::extract::Json<FirmwareChunkRequest>,
+) -> impl IntoResponse {
+    let success = crate::ota::write_chunk(input.0.offset, &input.0.data)
+        .await
+        .is_ok();
+    picoserve::response::Json(FirmwareResponse { success })
+}
+
+/// `POST /firmware/finish` - verify length, mark the image updated, and reset.
+async fn firmware_finish_handler() -> impl IntoResponse {
+    let success = crate::ota::finish().await.is_ok();
+    picoserve::response::Json(FirmwareResponse { success })
+}
+
+/// `GET /firmware/status` - progress for whichever update (HTTP or BLE) is in flight.
+async fn firmware_status_handler() -> impl IntoResponse {
+    picoserve::response::Json(FirmwareStatusResponse {
+        state: crate::ota::state() as u8,
+        bytes_received: crate::ota::bytes_received(),
+    })
+}
+
+/// The pin's role as currently persisted to flash (see `crate::config`), or `None` if it isn't
+/// configured at all.
+fn pin_role(pin_num: u8) -> Option<crate::config::PinRole> {
+    crate::config::load()
+        .pins
+        .into_iter()
+        .find(|(num, _)| *num == pin_num)
+        .map(|(_, role)| role)
+}
+
+/// `GET /pins` - a JSON snapshot of every pin currently configured, whatever role it's in.
+async fn pins_handler() -> impl IntoResponse {
+    let config = crate::config::load();
+    let pins = config
+        .pins
+        .into_iter()
+        .map(|(pin_num, _)| PinSnapshot {
+            pin_num,
+            state: load_pin_state(pin_num),
+        })
+        .collect();
+    picoserve::response::Json(PinsResponse { pins })
+}
+
+/// `GET /pin/{num}` - read back the state stored for a single pin.
+async fn read_pin_handler(picoserve::extract::Path(pin_num): picoserve::extract::Path<u8>) -> impl IntoResponse {
+    picoserve::response::Json(PinReadItem {
+        pin_num,
+        state: load_pin_state(pin_num) as i32,
+    })
+}
+
+/// `POST /pin/{num}?level=high|low` drives a basic output pin.
+/// `POST /pin/{num}?duty={0..100}` drives a PWM output pin.
+/// Rejected if the pin isn't currently configured as `output`/`pwm` - writing into a pin that
+/// `basic_read_pin_task`/`adc_read_pin_task` owns would just get immediately clobbered.
+async fn write_pin_handler(
+    picoserve::extract::Path(pin_num): picoserve::extract::Path<u8>,
+    picoserve::extract::Query(query): picoserve::extract::Query<WritePinQuery>,
+) -> impl IntoResponse {
+    if !matches!(
+        pin_role(pin_num),
+        Some(crate::config::PinRole::Output) | Some(crate::config::PinRole::Pwm)
+    ) {
+        return picoserve::response::Json(PinWriteResponse { success: false });
+    }
+
+    let state = if let Some(level) = query.level {
+        match level.as_str() {
+            "high" => Some(100),
+            "low" => Some(0),
+            _ => None,
+        }
+    } else {
+        query.duty.map(|duty| duty.min(100) as u32)
+    };
+
+    let Some(state) = state else {
+        return picoserve::response::Json(PinWriteResponse { success: false });
+    };
+
+    store_pin_state(pin_num, state);
+    picoserve::response::Json(PinWriteResponse { success: true })
+}
+
+fn load_pin_state(pin_num: u8) -> u32 {
+    match pin_num {
+        14 => crate::pin::GPIO14_STATE.load(Ordering::Relaxed),
+        26 => crate::pin::GPIO26_STATE.load(Ordering::Relaxed),
+        25 => crate::pin::GPIO25_STATE.load(Ordering::Relaxed),
+        33 => crate::pin::GPIO33_STATE.load(Ordering::Relaxed),
+        32 => crate::pin::GPIO32_STATE.load(Ordering::Relaxed),
+        35 => crate::pin::GPIO35_STATE.load(Ordering::Relaxed),
+        _ => 0,
+    }
+}
+
+fn store_pin_state(pin_num: u8, state: u32) {
+    match pin_num {
+        14 => crate::pin::GPIO14_STATE.store(state, Ordering::Relaxed),
+        26 => crate::pin::GPIO26_STATE.store(state, Ordering::Relaxed),
+        25 => crate::pin::GPIO25_STATE.store(state, Ordering::Relaxed),
+        33 => crate::pin::GPIO33_STATE.store(state, Ordering::Relaxed),
+        _ => {}
     }
 }
 
@@ -90,7 +320,12 @@ async fn read_pins_handler(input: picoserve::extract::Json<PinReadRequest>) -> i
     })
 }
 
-pub const WEB_TASK_POOL_SIZE: usize = 2;
+// Shared by every link the board serves over: 2 slots for the Wi-Fi stack plus 2 for the
+// optional WIZnet Ethernet stack (see `ethernet::start_ethernet`), each transport using its own
+// sub-range of task IDs.
+pub const WEB_TASK_POOL_SIZE: usize = 4;
+pub const WIFI_WEB_TASK_IDS: core::ops::Range<usize> = 0..2;
+pub const ETH_WEB_TASK_IDS: core::ops::Range<usize> = 2..4;
 
 #[embassy_executor::task(pool_size = WEB_TASK_POOL_SIZE)]
 pub async fn web_task(
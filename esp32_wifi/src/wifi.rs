@@ -12,15 +12,60 @@ use esp_radio::wifi::{
 };
 
 use crate::mk_static;
+use crate::provisioning;
+
+extern crate alloc;
+use alloc::string::String;
 
-const SSID: &str = env!("SSID");
-const PASSWORD: &str = env!("PASSWORD");
 // IP Address/Subnet mask eg: STATIC_IP=192.168.0.50/24
 const STATIC_IP: &str = env!("STATIC_IP");
 const GATEWAY_IP: &str = env!("GATEWAY_IP");
 
+/// How the network stack should obtain its IPv4 configuration.
+pub enum NetMode {
+    /// Fixed address/gateway, e.g. parsed from the `STATIC_IP`/`GATEWAY_IP` env vars.
+    Static { cidr: Ipv4Cidr, gateway: Ipv4Addr },
+    /// Lease an address from a DHCP server on the link.
+    Dhcp,
+}
+
+impl NetMode {
+    /// Build the `NetMode` that the compiled-in `STATIC_IP`/`GATEWAY_IP` env vars describe.
+    pub fn from_env() -> Self {
+        let Ok(cidr) = Ipv4Cidr::from_str(STATIC_IP) else {
+            println!("Invalid STATIC_IP");
+            loop {}
+        };
+
+        let Ok(gateway) = Ipv4Addr::from_str(GATEWAY_IP) else {
+            println!("Invalid GATEWAY_IP");
+            loop {}
+        };
+
+        NetMode::Static { cidr, gateway }
+    }
+
+    /// Build the `NetMode` that `crate::config::BoardConfig`'s `static_ip`/`gateway_ip` describe:
+    /// `Static` if both are set and parse, `Dhcp` otherwise - unlike `from_env`, a missing or
+    /// malformed config key just falls back to DHCP rather than hanging boot.
+    pub fn from_config(static_ip: Option<&str>, gateway_ip: Option<&str>) -> Self {
+        let (Some(static_ip), Some(gateway_ip)) = (static_ip, gateway_ip) else {
+            return NetMode::Dhcp;
+        };
+
+        let (Ok(cidr), Ok(gateway)) =
+            (Ipv4Cidr::from_str(static_ip), Ipv4Addr::from_str(gateway_ip))
+        else {
+            println!("Invalid static_ip/gateway_ip config, falling back to DHCP");
+            return NetMode::Dhcp;
+        };
+
+        NetMode::Static { cidr, gateway }
+    }
+}
+
 #[embassy_executor::task]
-async fn connection(mut controller: WifiController<'static>) {
+async fn connection(mut controller: WifiController<'static>, ssid: String, password: String) {
     println!("start connection task");
     println!("Device capabilities: {:?}", controller.capabilities());
     loop {
@@ -35,8 +80,8 @@ async fn connection(mut controller: WifiController<'static>) {
         if !matches!(controller.is_started(), Ok(true)) {
             let client_config = ModeConfig::Client(
                 ClientConfig::default()
-                    .with_ssid(SSID.into())
-                    .with_password(PASSWORD.into()),
+                    .with_ssid(ssid.as_str().into())
+                    .with_password(password.as_str().into()),
             );
             controller.set_config(&client_config).unwrap();
             println!("Starting wifi");
@@ -75,28 +120,28 @@ pub async fn start_wifi(
     wifi: esp_hal::peripherals::WIFI<'static>,
     rng: Rng,
     spawner: &Spawner,
+    net_mode: NetMode,
 ) -> Stack<'static> {
-    let (wifi_controller, interfaces) = esp_radio::wifi::new(radio_init, wifi, Default::default())
-        .expect("Failed to initialize Wi-Fi controller");
+    let (mut wifi_controller, interfaces) =
+        esp_radio::wifi::new(radio_init, wifi, Default::default())
+            .expect("Failed to initialize Wi-Fi controller");
+
+    // Credentials aren't baked in at compile time: use what's in flash, or run the SoftAP
+    // provisioning flow on this same controller until a client supplies and persists some.
+    let (ssid, password) =
+        provisioning::provision_if_needed(&mut wifi_controller, interfaces.ap, rng, spawner).await;
 
     let wifi_interface = interfaces.sta;
     let net_seed = rng.random() as u64 | ((rng.random() as u64) << 32);
 
-    let Ok(ip_addr) = Ipv4Cidr::from_str(STATIC_IP) else {
-        println!("Invalid STATIC_IP");
-        loop {}
+    let net_config = match net_mode {
+        NetMode::Static { cidr, gateway } => embassy_net::Config::ipv4_static(StaticConfigV4 {
+            address: cidr,
+            gateway: Some(gateway),
+            dns_servers: Default::default(),
+        }),
+        NetMode::Dhcp => embassy_net::Config::dhcpv4(Default::default()),
     };
-
-    let Ok(gateway) = Ipv4Addr::from_str(GATEWAY_IP) else {
-        println!("Invalid GATEWAY_IP");
-        loop {}
-    };
-
-    let net_config = embassy_net::Config::ipv4_static(StaticConfigV4 {
-        address: ip_addr,
-        gateway: Some(gateway),
-        dns_servers: Default::default(),
-    });
     // Init network stack
     let (stack, runner) = embassy_net::new(
         wifi_interface,
@@ -105,7 +150,7 @@ pub async fn start_wifi(
         net_seed,
     );
 
-    spawner.spawn(connection(wifi_controller)).ok();
+    spawner.spawn(connection(wifi_controller, ssid, password)).ok();
     spawner.spawn(net_task(runner)).ok();
 
     wait_for_connection(stack).await;
@@ -122,10 +167,12 @@ async fn wait_for_connection(stack: Stack<'_>) {
         Timer::after(Duration::from_millis(500)).await;
     }
 
-    println!("Waiting to get IP address...");
+    println!("Waiting to get IP address (and lease, if DHCP)...");
     loop {
         if let Some(config) = stack.config_v4() {
             println!("Got IP: {}", config.address);
+            println!("Gateway: {:?}", config.gateway);
+            println!("DNS servers: {:?}", config.dns_servers);
             break;
         }
         Timer::after(Duration::from_millis(500)).await;